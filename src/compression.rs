@@ -0,0 +1,129 @@
+use tokio::io::AsyncWriteExt;
+use async_compression::tokio::write::{BrotliEncoder, GzipEncoder};
+
+/// # Encoding
+///
+/// The content codings this server knows how to produce.
+///
+/// Ordered by preference: if a client accepts both, `Brotli` wins over `Gzip`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding{
+    Brotli,
+    Gzip
+}
+
+impl Encoding{
+    /// The value to send back in the `Content-Encoding` header.
+    pub fn header_value(&self) -> &'static str{
+        match self{
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip"
+        }
+    }
+}
+
+/// Bodies smaller than this are served uncompressed - the gzip/brotli framing
+/// overhead tends to make tiny payloads larger, not smaller.
+pub const MIN_COMPRESSIBLE_SIZE: usize = 256;
+
+/// # Is Compressible
+///
+/// Decides whether a response body is worth compressing, based on its
+/// `Content-Type`. Formats that are already compressed (images, fonts,
+/// archives) are left alone.
+pub fn is_compressible(content_type: &str) -> bool{
+    let content_type = content_type.split(';').next().unwrap_or("").trim();
+
+    content_type.starts_with("text/")
+        || content_type == "application/json"
+        || content_type == "application/javascript"
+        || content_type == "image/svg+xml"
+}
+
+/// # Negotiate
+///
+/// Picks the best coding this server supports out of the client's
+/// `Accept-Encoding` list (see `Request::accept_encoding`), preferring `br`
+/// over `gzip` when both are acceptable. Returns `None` if the client accepts
+/// neither, or rejects both with `q=0`.
+pub fn negotiate(accepted: &[(String, f32)]) -> Option<Encoding>{
+    let acceptable = |coding: &str| {
+        accepted.iter()
+            .find(|(name, _)| name == coding)
+            .map(|(_, q)| *q > 0.0)
+            .unwrap_or(false)
+    };
+
+    if acceptable("br"){
+        Some(Encoding::Brotli)
+    }else if acceptable("gzip"){
+        Some(Encoding::Gzip)
+    }else{
+        None
+    }
+}
+
+/// # Compress
+///
+/// Compresses `data` with the given coding, ready to be sent alongside a
+/// matching `Content-Encoding` header.
+pub async fn compress(data: &[u8], encoding: Encoding) -> std::io::Result<Vec<u8>>{
+    match encoding{
+        Encoding::Gzip => {
+            let mut encoder = GzipEncoder::new(Vec::new());
+            encoder.write_all(data).await?;
+            encoder.shutdown().await?;
+            Ok(encoder.into_inner())
+        }
+        Encoding::Brotli => {
+            let mut encoder = BrotliEncoder::new(Vec::new());
+            encoder.write_all(data).await?;
+            encoder.shutdown().await?;
+            Ok(encoder.into_inner())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn text_and_json_and_svg_are_compressible(){
+        assert!(is_compressible("text/html"));
+        assert!(is_compressible("text/html; charset=utf-8"));
+        assert!(is_compressible("application/json"));
+        assert!(is_compressible("image/svg+xml"));
+    }
+
+    #[test]
+    fn images_and_fonts_are_not_compressible(){
+        assert!(!is_compressible("image/png"));
+        assert!(!is_compressible("font/woff2"));
+        assert!(!is_compressible("application/octet-stream"));
+    }
+
+    #[test]
+    fn negotiate_prefers_brotli_over_gzip(){
+        let accepted = vec![("gzip".to_string(), 1.0), ("br".to_string(), 1.0)];
+        assert_eq!(negotiate(&accepted), Some(Encoding::Brotli));
+    }
+
+    #[test]
+    fn negotiate_falls_back_to_gzip(){
+        let accepted = vec![("gzip".to_string(), 1.0)];
+        assert_eq!(negotiate(&accepted), Some(Encoding::Gzip));
+    }
+
+    #[test]
+    fn negotiate_honours_q_zero_rejection(){
+        let accepted = vec![("br".to_string(), 0.0), ("gzip".to_string(), 1.0)];
+        assert_eq!(negotiate(&accepted), Some(Encoding::Gzip));
+    }
+
+    #[test]
+    fn negotiate_returns_none_when_nothing_is_acceptable(){
+        let accepted = vec![("deflate".to_string(), 1.0)];
+        assert_eq!(negotiate(&accepted), None);
+    }
+}
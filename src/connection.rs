@@ -0,0 +1,149 @@
+use tokio::net::TcpStream;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// # Connection
+///
+/// Wraps a single accepted `TcpStream` for the lifetime of one request/response
+/// (or, after an upgrade, hands the stream off entirely - see `into_inner`).
+pub struct Connection{
+    stream: TcpStream
+}
+
+impl Connection{
+    /// # New
+    ///
+    /// Wrap a freshly accepted `TcpStream`.
+    pub fn new(stream: TcpStream) -> Self{
+        Self{ stream }
+    }
+
+    /// # Into Inner
+    ///
+    /// Unwraps the underlying `TcpStream`, eg to hand it off to a `WebSocket`
+    /// once the upgrade handshake has been written.
+    pub(crate) fn into_inner(self) -> TcpStream{
+        self.stream
+    }
+
+    /// # Read Request
+    ///
+    /// Reads a full HTTP request off the stream, as raw bytes - the body may be
+    /// an arbitrary binary payload, so nothing here ever decodes it as text.
+    /// A single `read` call only ever returns whatever happens to be sitting in
+    /// the socket buffer, which is rarely an entire request in one go - so this
+    /// keeps reading: first until the `\r\n\r\n` header terminator has arrived,
+    /// then, if the headers advertise a `Content-Length` longer than the body
+    /// read so far, until enough additional bytes have come in to satisfy it.
+    pub async fn read_request(&mut self) -> Vec<u8>{
+        let mut buffer = Vec::new();
+        let mut chunk = [0u8; 4096];
+
+        let header_end = loop{
+            match self.stream.read(&mut chunk).await{
+                Ok(0) => return buffer,
+                Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                Err(_) => return buffer
+            }
+
+            if let Some(i) = find_header_terminator(&buffer){
+                break i;
+            }
+        };
+
+        let content_length = Self::content_length(&buffer[..header_end]);
+        let body_so_far = buffer.len() - (header_end + 4);
+        let mut remaining = content_length.saturating_sub(body_so_far);
+
+        while remaining > 0{
+            match self.stream.read(&mut chunk).await{
+                Ok(0) => break,
+                Ok(n) => {
+                    buffer.extend_from_slice(&chunk[..n]);
+                    remaining = remaining.saturating_sub(n);
+                }
+                Err(_) => break
+            }
+        }
+
+        buffer
+    }
+
+    /// # Write String
+    ///
+    /// Writes a text response back to the client.
+    pub async fn write_string(&mut self, content: String) -> tokio::io::Result<()>{
+        self.stream.write_all(content.as_bytes()).await?;
+        self.stream.flush().await
+    }
+
+    /// # Write Bytes
+    ///
+    /// Writes a binary response back to the client.
+    pub async fn write_bytes(&mut self, content: Vec<u8>) -> tokio::io::Result<()>{
+        self.stream.write_all(&content).await?;
+        self.stream.flush().await
+    }
+
+    fn content_length(headers: &[u8]) -> usize{
+        String::from_utf8_lossy(headers)
+            .split("\r\n")
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.eq_ignore_ascii_case("content-length").then(|| value.trim().parse().ok()).flatten()
+            })
+            .unwrap_or(0)
+    }
+}
+
+fn find_header_terminator(buffer: &[u8]) -> Option<usize>{
+    buffer.windows(4).position(|w| w == b"\r\n\r\n")
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn read_request_round_trips_a_non_utf8_body(){
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let body = vec![0xFF, 0xFE, 0x00, 0x01];
+        let mut request = b"POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\n".to_vec();
+        request.extend_from_slice(&body);
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let (server_stream, _) = listener.accept().await.unwrap();
+
+        client.write_all(&request).await.unwrap();
+        client.flush().await.unwrap();
+        drop(client);
+
+        let mut connection = Connection::new(server_stream);
+        let received = connection.read_request().await;
+
+        assert_eq!(&received[received.len() - 4..], body.as_slice());
+    }
+
+    #[test]
+    fn content_length_reads_the_header(){
+        assert_eq!(Connection::content_length(b"POST / HTTP/1.1\r\nContent-Length: 42\r\n"), 42);
+    }
+
+    #[test]
+    fn content_length_is_case_insensitive(){
+        assert_eq!(Connection::content_length(b"POST / HTTP/1.1\r\ncontent-length: 7\r\n"), 7);
+    }
+
+    #[test]
+    fn content_length_defaults_to_zero_when_absent(){
+        assert_eq!(Connection::content_length(b"GET / HTTP/1.1\r\nHost: example.com\r\n"), 0);
+    }
+
+    #[test]
+    fn finds_header_terminator(){
+        assert_eq!(find_header_terminator(b"GET / HTTP/1.1\r\n\r\nbody"), Some(14));
+        assert_eq!(find_header_terminator(b"GET / HTTP/1.1\r\n"), None);
+    }
+}
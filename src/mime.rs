@@ -0,0 +1,47 @@
+/// # From Extension
+///
+/// Maps a file extension (without the leading `.`) to its MIME type, falling
+/// back to `application/octet-stream` for anything not in the table.
+pub fn from_extension(extension: &str) -> &'static str{
+    match extension.to_ascii_lowercase().as_str(){
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" => "application/javascript",
+        "json" => "application/json",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        _ => "application/octet-stream"
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn known_extensions_map_to_their_mime_type(){
+        assert_eq!(from_extension("html"), "text/html");
+        assert_eq!(from_extension("js"), "application/javascript");
+        assert_eq!(from_extension("png"), "image/png");
+    }
+
+    #[test]
+    fn extension_matching_is_case_insensitive(){
+        assert_eq!(from_extension("HTML"), "text/html");
+        assert_eq!(from_extension("Png"), "image/png");
+    }
+
+    #[test]
+    fn unknown_extension_falls_back_to_octet_stream(){
+        assert_eq!(from_extension("exe"), "application/octet-stream");
+        assert_eq!(from_extension(""), "application/octet-stream");
+    }
+}
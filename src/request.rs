@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 
 /// # Http Methods
 /// 
@@ -31,19 +32,40 @@ pub struct Request{
     pub method: Option<HttpMethod>,
     pub uri: String,
     pub user_agent: String,
+    /// The codings listed in the `Accept-Encoding` header, ordered from most to
+    /// least preferred (highest `q` first). A coding with `q=0` is present but
+    /// explicitly rejected by the client.
+    pub accept_encoding: Vec<(String, f32)>,
+    /// The value of the `Sec-WebSocket-Key` header, present only on a WebSocket
+    /// upgrade request. Use `is_websocket_upgrade` to check the whole handshake.
+    pub sec_websocket_key: Option<String>,
+    /// Parsed form fields, populated when `Content-Type` is
+    /// `application/x-www-form-urlencoded`. Empty for any other content type -
+    /// use `raw_body` or `json` instead.
+    pub post_request: HashMap<String, String>,
+    /// The request body, exactly as received, for content types `post_request`
+    /// and `json` don't cover.
+    pub raw_body: Vec<u8>,
     pub raw_request: Vec::<String>
 }
 
 impl Request{
     /// # New
-    /// 
-    /// Create a new request struct. 
-    /// 
-    /// Takes an input string (Which should be
-    /// the request).
-    pub fn new(request: String) -> Self{
-        
-        let request = Request::split_to_row(request);
+    ///
+    /// Create a new request struct.
+    ///
+    /// Takes the raw request bytes off the wire, with the header block and
+    /// body (if any) separated by the first blank line, per RFC 7230. Only the
+    /// header block is decoded as text - the body is kept as the exact bytes
+    /// the client sent, since it may be an arbitrary binary payload.
+    pub fn new(request: Vec<u8>) -> Self{
+
+        let (header_block, mut body) = match request.windows(4).position(|w| w == b"\r\n\r\n"){
+            Some(i) => (String::from_utf8_lossy(&request[..i]).into_owned(), request[i + 4..].to_vec()),
+            None => (String::from_utf8_lossy(&request).into_owned(), Vec::new())
+        };
+
+        let request = Request::split_to_row(header_block);
 
         let method = Request::get_method(&request);
 
@@ -51,15 +73,66 @@ impl Request{
 
         let user_agent = Request::get_user_agent(&request);
 
+        let accept_encoding = Request::get_accept_encoding(&request);
+
+        let sec_websocket_key = Request::get_header(&request, "Sec-WebSocket-Key:");
+
+        let content_type = Request::get_header(&request, "Content-Type:").unwrap_or_default();
+
+        // `Connection::read_request` already keeps reading off the stream until
+        // it has `Content-Length` bytes of body, so this is just a defensive
+        // clamp against a pipelined request leaking its next request's bytes in.
+        if let Some(content_length) = Request::get_header(&request, "Content-Length:").and_then(|v| v.parse::<usize>().ok()){
+            body.truncate(content_length);
+        }
+
+        let post_request = if content_type.to_ascii_lowercase().starts_with("application/x-www-form-urlencoded"){
+            Request::get_post_request(&body)
+        }else{
+            HashMap::new()
+        };
+
 
         Self{
             method,
-            uri, 
+            uri,
             user_agent,
+            accept_encoding,
+            sec_websocket_key,
+            post_request,
+            raw_body: body,
             raw_request: request
         }
     }
 
+    /// # Json
+    ///
+    /// Deserializes the request body as JSON into `T`.
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> serde_json::Result<T>{
+        serde_json::from_slice(&self.raw_body)
+    }
+
+    /// # Is Websocket Upgrade
+    ///
+    /// Whether this request is asking to be upgraded to a WebSocket connection,
+    /// ie. it carries `Connection: Upgrade`, `Upgrade: websocket` and a
+    /// `Sec-WebSocket-Key`.
+    pub fn is_websocket_upgrade(&self) -> bool{
+        if self.sec_websocket_key.is_none(){
+            return false;
+        }
+
+        let has_connection_upgrade = Request::get_header(&self.raw_request, "Connection:")
+            .map(|v| v.to_ascii_lowercase().contains("upgrade"))
+            .unwrap_or(false);
+
+        let has_upgrade_websocket = Request::get_header(&self.raw_request, "Upgrade:")
+            .map(|v| v.to_ascii_lowercase().contains("websocket"))
+            .unwrap_or(false);
+
+        has_connection_upgrade && has_upgrade_websocket
+    }
+
     fn split_to_row(string: String) -> Vec::<String>{
         let strings: Vec::<String> = string.split("\r\n").map(|x| x.to_string()).collect();
 
@@ -136,5 +209,181 @@ impl Request{
 
         agent
     }
-    
+
+    /// # Get Header
+    ///
+    /// Finds a header by its name (including the trailing `:`, eg `"Upgrade:"`)
+    /// and returns its value, trimmed. Used for the one-off headers that don't
+    /// warrant their own dedicated parser.
+    fn get_header(strings: &[String], name: &str) -> Option<String>{
+        for string in strings.iter(){
+            if string.len() >= name.len() && string[..name.len()].eq_ignore_ascii_case(name){
+                return Some(string[name.len()..].trim().to_string());
+            }
+        }
+
+        None
+    }
+
+    /// # Get Accept Encoding
+    ///
+    /// Parses the `Accept-Encoding` header, if present, into a list of
+    /// `(coding, q-value)` pairs sorted by descending `q`. Codings without an
+    /// explicit `;q=` are given a `q` of `1.0`, per RFC 7231.
+    fn get_accept_encoding(strings: &[String]) -> Vec<(String, f32)>{
+        let mut codings = Vec::new();
+
+        if let Some(value) = Request::get_header(strings, "Accept-Encoding:"){
+            for coding in value.split(','){
+                let mut parts = coding.trim().split(";q=");
+                let name = match parts.next(){
+                    Some(name) if !name.is_empty() => name.trim().to_string(),
+                    _ => continue
+                };
+                let q: f32 = parts.next()
+                    .and_then(|q| q.trim().parse().ok())
+                    .unwrap_or(1.0);
+
+                codings.push((name, q));
+            }
+        }
+
+        codings.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        codings
+    }
+
+    /// # Get Post Request
+    ///
+    /// Parses an `application/x-www-form-urlencoded` body (`key=value&key=value`)
+    /// into a hashmap, URL-decoding both keys and values.
+    fn get_post_request(body: &[u8]) -> HashMap<String, String>{
+        let mut post_request = HashMap::new();
+
+        for pair in body.split(|&b| b == b'&'){
+            if pair.is_empty(){
+                continue;
+            }
+
+            let mut parts = pair.splitn(2, |&b| b == b'=');
+            let key = parts.next().unwrap_or(&[]);
+            let value = parts.next().unwrap_or(&[]);
+
+            post_request.insert(Request::url_decode(key), Request::url_decode(value));
+        }
+
+        post_request
+    }
+
+    /// # Url Decode
+    ///
+    /// Decodes a `%XX`/`+`-encoded form field into its original bytes, then
+    /// lossily interprets them as UTF-8.
+    fn url_decode(value: &[u8]) -> String{
+        let mut decoded = Vec::with_capacity(value.len());
+        let mut i = 0;
+
+        while i < value.len(){
+            match value[i]{
+                b'+' => decoded.push(b' '),
+                b'%' => {
+                    let hi = value.get(i + 1).and_then(|&b| (b as char).to_digit(16));
+                    let lo = value.get(i + 2).and_then(|&b| (b as char).to_digit(16));
+                    match (hi, lo){
+                        (Some(hi), Some(lo)) => {
+                            decoded.push((hi * 16 + lo) as u8);
+                            i += 2;
+                        }
+                        _ => decoded.push(b'%')
+                    }
+                }
+                byte => decoded.push(byte)
+            }
+            i += 1;
+        }
+
+        String::from_utf8_lossy(&decoded).into_owned()
+    }
+
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn accept_encoding_defaults_unqualified_codings_to_q_1(){
+        let strings = vec!["Accept-Encoding: gzip, br".to_string()];
+        assert_eq!(Request::get_accept_encoding(&strings), vec![
+            ("gzip".to_string(), 1.0),
+            ("br".to_string(), 1.0),
+        ]);
+    }
+
+    #[test]
+    fn accept_encoding_sorts_by_descending_q(){
+        let strings = vec!["Accept-Encoding: gzip;q=0.5, br;q=0.8, deflate;q=1.0".to_string()];
+        assert_eq!(Request::get_accept_encoding(&strings), vec![
+            ("deflate".to_string(), 1.0),
+            ("br".to_string(), 0.8),
+            ("gzip".to_string(), 0.5),
+        ]);
+    }
+
+    #[test]
+    fn accept_encoding_is_empty_when_header_absent(){
+        let strings = vec!["Host: example.com".to_string()];
+        assert!(Request::get_accept_encoding(&strings).is_empty());
+    }
+
+    #[test]
+    fn accept_encoding_is_case_insensitive(){
+        let strings = vec!["accept-encoding: gzip, br".to_string()];
+        assert_eq!(Request::get_accept_encoding(&strings), vec![
+            ("gzip".to_string(), 1.0),
+            ("br".to_string(), 1.0),
+        ]);
+    }
+
+    #[test]
+    fn url_decode_handles_percent_and_plus(){
+        assert_eq!(Request::url_decode(b"hello%20world"), "hello world");
+        assert_eq!(Request::url_decode(b"a+b"), "a b");
+    }
+
+    #[test]
+    fn url_decode_passes_through_a_trailing_percent_with_no_hex_digits(){
+        assert_eq!(Request::url_decode(b"100%"), "100%");
+    }
+
+    #[test]
+    fn url_decode_passes_through_invalid_hex_digits(){
+        assert_eq!(Request::url_decode(b"100%zz"), "100%zz");
+    }
+
+    #[test]
+    fn get_post_request_splits_and_decodes_pairs(){
+        let post = Request::get_post_request(b"name=John+Doe&city=New%20York");
+        assert_eq!(post.get("name"), Some(&"John Doe".to_string()));
+        assert_eq!(post.get("city"), Some(&"New York".to_string()));
+    }
+
+    #[test]
+    fn get_post_request_ignores_empty_pairs(){
+        let post = Request::get_post_request(b"a=1&&b=2&");
+        assert_eq!(post.len(), 2);
+        assert_eq!(post.get("a"), Some(&"1".to_string()));
+        assert_eq!(post.get("b"), Some(&"2".to_string()));
+    }
+
+    #[test]
+    fn raw_body_round_trips_non_utf8_bytes(){
+        let body: Vec<u8> = vec![0xFF, 0xFE, 0x00, 0x01];
+        let mut raw = b"POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\n".to_vec();
+        raw.extend_from_slice(&body);
+
+        let request = Request::new(raw);
+
+        assert_eq!(request.raw_body, body);
+    }
 }
\ No newline at end of file
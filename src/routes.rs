@@ -1,9 +1,19 @@
 use std::collections::HashMap;
 use std::future::Future;
+use std::sync::Arc;
 use crate::Request;
-use tokio::io::AsyncReadExt;
-use chunked_transfer::Encoder;
-use std::io::Write;
+use crate::compression;
+use crate::mime;
+use crate::WebSocket;
+
+/// A route callback: takes the shared application state and the `Request`, and
+/// returns the response body to send back.
+type Handler<S> = std::pin::Pin<Box<dyn Fn(Arc<S>, Request) -> std::pin::Pin<Box<dyn Future<Output = Result<String, String>> + Send>> + Send + Sync>>;
+
+/// A websocket route callback: takes the shared application state, the upgraded
+/// `Request` and the `WebSocket` connection, and runs for as long as it wants to
+/// keep talking to the client.
+type WsHandler<S> = std::pin::Pin<Box<dyn Fn(Arc<S>, Request, WebSocket) -> std::pin::Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>>;
 
 
 /// # DataType
@@ -21,78 +31,89 @@ pub enum DataType{
 }
 
 /// # Routes
-/// 
+///
 /// This struct defines the routes. It uses a hashmap to do this.
-/// 
+///
 /// `HashMap<Route, Content>` where content is the return content (ie, html or json).
-pub struct Routes{
-    routes: HashMap::<String, std::pin::Pin<Box<dyn Fn(Request) -> std::pin::Pin<Box<dyn Future<Output = Result<String, String>> + Send>>>>>
+///
+/// `S` is the shared application state type - see `HttpServer`'s docs for how
+/// it gets to a handler.
+pub struct Routes<S>{
+    routes: HashMap::<String, Handler<S>>,
+    ws_routes: HashMap::<String, WsHandler<S>>,
+    /// The route prefix (eg `"/static"`) and filesystem directory it's served
+    /// from, set up via `set_static_dir`.
+    static_dir: Option<(String, String)>
 }
 
-impl Routes{
+impl<S> Routes<S> where S: Send + Sync + 'static{
     /// # New
-    /// 
+    ///
     /// Create a new `Route` struct
     pub async fn new() -> Self{
         Self{
-            routes: HashMap::<String, std::pin::Pin<Box<dyn Fn(Request) -> std::pin::Pin<Box<dyn Future<Output = Result<String, String>> + Send>>>>>::new()
+            routes: HashMap::<String, Handler<S>>::new(),
+            ws_routes: HashMap::<String, WsHandler<S>>::new(),
+            static_dir: None
         }
     }
 
+    /// # Set Static Dir
+    ///
+    /// Serves files out of `fs_dir` on disk whenever a request's URI starts with
+    /// `route_prefix` (eg `set_static_dir("/static".to_string(), "./static".to_string())`
+    /// serves `./static/img.png` for a request to `/static/img.png`).
+    ///
+    /// The `Content-Type` is derived from the file's extension (see the `mime`
+    /// module), and any URI whose path under the prefix contains `..` is refused
+    /// with a `400 Bad Request` rather than allowed to escape `fs_dir`.
+    pub async fn set_static_dir(&mut self, route_prefix: String, fs_dir: String){
+        self.static_dir = Some((route_prefix, fs_dir));
+    }
+
     /// # Add Route
-    /// 
+    ///
     /// Adds a new route to the routes hashmap. If the route already exists,
     /// its value is updated
-    pub async fn add_route(&mut self, route: String, content: std::pin::Pin<Box<dyn Fn(Request) -> std::pin::Pin<Box<dyn Future<Output = Result<String, String>> + Send>>>>){
+    pub async fn add_route(&mut self, route: String, content: Handler<S>){
         self.routes.insert(route, content);
     }
 
+    /// # Add Ws Route
+    ///
+    /// Adds a new websocket route, distinct from the normal routes registered via
+    /// `add_route`. If the route already exists, its handler is updated.
+    pub async fn add_ws_route(&mut self, route: String, content: WsHandler<S>){
+        self.ws_routes.insert(route, content);
+    }
+
+    /// # Get Ws Handler
+    ///
+    /// Looks up the websocket handler registered for `uri`, if any. Called by
+    /// `HttpServer` once it has completed the upgrade handshake.
+    pub(crate) fn get_ws_handler(&self, uri: &str) -> Option<&WsHandler<S>>{
+        self.ws_routes.get(uri)
+    }
+
     /// # Get Route
-    /// 
-    /// This function takes in the response string from the `TcpStream` and searches the hashmap
-    /// for the callback function associated with the route. It then checks that the route is valid,
-    /// and runs it asynchrynously (using the request so that the callback can make use of the request data)
-    /// 
+    ///
+    /// This function takes in the raw request bytes read off the `TcpStream` and searches the
+    /// hashmap for the callback function associated with the route. It then checks that the route
+    /// is valid, and runs it asynchrynously (using the request so that the callback can make use
+    /// of the request data)
+    ///
     /// This function only runs the callback - handling POST and GET requests is up to the callback.
-    /// 
-    /// If this function detects a request for static content - which it can only detect if the data is stored in
-    /// `/static/`, then it will return early with the static content, and not run any functions.
-    pub async fn get_route(&self, request: String, user_addr: std::net::SocketAddr) -> Result<DataType, &str>{
-        let request = Request::new(request, user_addr);
+    ///
+    /// If a static directory has been configured via `set_static_dir` and the request's URI falls
+    /// under its route prefix, it returns early with the file's content, and does not run any
+    /// registered callback.
+    pub async fn get_route(&self, request: Vec<u8>, _user_addr: std::net::SocketAddr, state: Arc<S>) -> Result<DataType, &str>{
+        let request = Request::new(request);
+        let accept_encoding = request.accept_encoding.clone();
 
         // Handle static files
-        if request.uri.contains("static"){
-            let file_path = format!(".{}", request.uri);
-            return match tokio::fs::File::open(file_path).await{
-                Ok(mut file_handle) => {
-                    let mut contents = vec![];
-                    file_handle.read_to_end(&mut contents).await.unwrap();
-                    let result = String::from("HTTP/1.1 {} {}\r\nContent-type: image/jpeg;\r\nTransfer-Encoding: chunked\r\n\r\n");
-                    let mut result = result.into_bytes();
-                    let mut encoded = Vec::new();
-                    {
-                        let mut encoder = Encoder::with_chunks_size(&mut encoded, 8);
-                        encoder.write_all(&contents).unwrap();
-                    }
-                    result.extend(&encoded);
-                    match String::from_utf8(result.clone()){
-                        Ok(_) => {
-                            let result = String::from("HTTP/1.1 {} {}\r\nContent-type: text/css;\r\nTransfer-Encoding: chunked\r\n\r\n");
-                            let mut result = result.into_bytes();
-                            result.extend(&encoded);
-                            let v = String::from_utf8(result).expect("This should work");
-                            return Ok(DataType::Text(v))
-                        }
-                        Err(_) => {
-                            return Ok(DataType::Bytes(result))
-                        }
-                    }
-                }
-                Err(e) => {
-                    println!("Error loading static content: {}", e);
-                    Ok(DataType::Text(String::from("ERROR - CONTENT NOT AVAILABLE")))
-                }
-            };
+        if let Some(result) = self.serve_static(&request.uri).await{
+            return Ok(Self::apply_compression(result, &accept_encoding).await);
         }
 
         // If not static, handle the request
@@ -101,16 +122,198 @@ impl Routes{
             None => {
                 println!("Error - user requested '{}', which does not exist on this server.", request.uri);
                 self.routes.get(&"err".to_string()).unwrap()// we assume we've got an error handler
-            } 
+            }
         };
-           
+
         // Check that our function returned an Ok result, and unwrap it after it executes
-        let result = if let Ok(v) = func(request).await{
-            return Ok(DataType::Text(v));
-        }else{
-            DataType::Text(String::new()) // Err returned, just return nothing
+        let result = match func(state, request).await{
+            Ok(v) => DataType::Text(v),
+            Err(_) => DataType::Text(String::new()) // Err returned, just return nothing
         };
 
-        Ok(result)
+        Ok(Self::apply_compression(result, &accept_encoding).await)
+    }
+
+    /// # Serve Static
+    ///
+    /// Serves `uri` out of the configured static directory, if one is set up via
+    /// `set_static_dir` and `uri` falls under its route prefix. Returns `None` when
+    /// static serving isn't configured or the URI is outside the prefix - in either
+    /// case the caller should fall through to the registered route callbacks.
+    ///
+    /// The `Content-Type` is derived from the file's extension (see the `mime`
+    /// module), and the path under the prefix is rejected with a `400 Bad Request`
+    /// if it contains `..`, so a client can't escape the configured directory.
+    async fn serve_static(&self, uri: &str) -> Option<DataType>{
+        let (route_prefix, fs_dir) = self.static_dir.as_ref()?;
+        let relative_path = uri.strip_prefix(route_prefix.as_str())?;
+
+        // Require a path boundary right after the prefix, so `/static` doesn't
+        // also swallow `/staticFoo` (and shadow a route registered at that URI).
+        if !relative_path.is_empty() && !relative_path.starts_with('/'){
+            return None;
+        }
+
+        if relative_path.contains(".."){
+            return Some(DataType::Text(String::from("HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")));
+        }
+
+        let file_path = std::path::Path::new(fs_dir).join(relative_path.trim_start_matches('/'));
+
+        let contents = match tokio::fs::read(&file_path).await{
+            Ok(contents) => contents,
+            Err(e) => {
+                println!("Error loading static content '{}': {}", file_path.display(), e);
+                return Some(DataType::Text(String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")));
+            }
+        };
+
+        let content_type = file_path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(mime::from_extension)
+            .unwrap_or("application/octet-stream");
+
+        let headers = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+            content_type, contents.len()
+        );
+
+        let mut result = headers.into_bytes();
+        result.extend(contents);
+
+        Some(DataType::Bytes(result))
+    }
+
+    /// # Apply Compression
+    ///
+    /// Negotiates a content coding against the client's `Accept-Encoding` list and,
+    /// if the response is worth compressing (compressible `Content-Type`, body at
+    /// least `compression::MIN_COMPRESSIBLE_SIZE` bytes), rewrites the response with
+    /// a compressed body and a matching `Content-Encoding`/`Content-Length` header.
+    ///
+    /// Responses that already carry a `Transfer-Encoding` header are left alone -
+    /// their body is chunk-framed rather than raw, so compressing it as-is and then
+    /// adding `Content-Length` would produce an invalid HTTP/1.1 message.
+    async fn apply_compression(data: DataType, accept_encoding: &[(String, f32)]) -> DataType{
+        let bytes: &[u8] = match &data{
+            DataType::Text(text) => text.as_bytes(),
+            DataType::Bytes(bytes) => bytes
+        };
+
+        let header_end = match bytes.windows(4).position(|w| w == b"\r\n\r\n"){
+            Some(i) => i,
+            None => return data
+        };
+
+        let (headers, body) = bytes.split_at(header_end);
+        let body = &body[4..];
+        let headers = String::from_utf8_lossy(headers);
+
+        let content_type = headers.split("\r\n")
+            .find_map(|line| line.to_ascii_lowercase().starts_with("content-type:").then(|| {
+                line.split_once(':').map(|(_, value)| value).unwrap_or("").trim().to_string()
+            }))
+            .unwrap_or_default();
+
+        let is_chunked = headers.split("\r\n")
+            .any(|line| line.to_ascii_lowercase().starts_with("transfer-encoding:"));
+
+        if is_chunked || body.len() < compression::MIN_COMPRESSIBLE_SIZE || !compression::is_compressible(&content_type){
+            return data;
+        }
+
+        let encoding = match compression::negotiate(accept_encoding){
+            Some(encoding) => encoding,
+            None => return data
+        };
+
+        let compressed = match compression::compress(body, encoding).await{
+            Ok(compressed) => compressed,
+            Err(_) => return data
+        };
+
+        let mut new_headers = String::new();
+        for line in headers.split("\r\n"){
+            let lower = line.to_ascii_lowercase();
+            if lower.starts_with("content-length:") || lower.starts_with("content-encoding:"){
+                continue;
+            }
+            new_headers.push_str(line);
+            new_headers.push_str("\r\n");
+        }
+        new_headers.push_str(&format!("Content-Encoding: {}\r\n", encoding.header_value()));
+        new_headers.push_str(&format!("Content-Length: {}\r\n\r\n", compressed.len()));
+
+        let mut result = new_headers.into_bytes();
+        result.extend(compressed);
+
+        DataType::Bytes(result)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    async fn routes_with_static_dir() -> (Routes<()>, std::path::PathBuf){
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let id = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+        let dir = std::env::temp_dir().join(format!("micro_http_async_test_{}_{}", std::process::id(), id));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        tokio::fs::write(dir.join("img.png"), b"not really a png").await.unwrap();
+
+        let mut routes = Routes::<()>::new().await;
+        routes.set_static_dir("/static".to_string(), dir.to_string_lossy().into_owned()).await;
+
+        (routes, dir)
+    }
+
+    #[tokio::test]
+    async fn serves_a_file_under_the_prefix(){
+        let (routes, dir) = routes_with_static_dir().await;
+
+        let result = routes.serve_static("/static/img.png").await;
+        assert!(matches!(result, Some(DataType::Bytes(_))));
+
+        tokio::fs::remove_dir_all(dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn does_not_match_a_uri_that_merely_starts_with_the_prefix(){
+        let (routes, dir) = routes_with_static_dir().await;
+
+        // `/staticFoo` shares a string prefix with `/static` but isn't under it -
+        // it must fall through to a registered route instead of being served
+        // (or mis-served) as a static file.
+        let result = routes.serve_static("/staticFoo").await;
+        assert!(result.is_none());
+
+        tokio::fs::remove_dir_all(dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_path_traversal(){
+        let (routes, dir) = routes_with_static_dir().await;
+
+        let result = routes.serve_static("/static/../secret").await;
+        assert!(matches!(result, Some(DataType::Text(text)) if text.starts_with("HTTP/1.1 400")));
+
+        tokio::fs::remove_dir_all(dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn leaves_chunked_responses_uncompressed(){
+        let body = "x".repeat(compression::MIN_COMPRESSIBLE_SIZE);
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nTransfer-Encoding: chunked\r\n\r\n{}",
+            body
+        );
+
+        let accept_encoding = vec![("gzip".to_string(), 1.0)];
+        let result = Routes::<()>::apply_compression(DataType::Text(response.clone()), &accept_encoding).await;
+
+        assert!(matches!(result, DataType::Text(text) if text == response));
     }
 }
\ No newline at end of file
@@ -1,73 +1,149 @@
 use tokio::net::{TcpListener, TcpStream}; // Async versions of the stdlib implementation
 use tokio::io; // :D
+use std::sync::Arc;
 
 use crate::Connection;
 use crate::Routes;
+use crate::Request;
+use crate::websocket;
 
 /// # HTTP Server
-/// 
+///
 /// This struct stores the listener, which listens for incoming connections and handles them
-/// 
+///
+/// `S` is the type of the shared application state, handed to every route callback as
+/// an `Arc<S>` so handlers can reach a database pool, config, or cache without resorting
+/// to global statics. Use `()` if your handlers don't need any shared state.
+///
 /// **Example**:
-/// 
+///
 /// ```
-/// let http_server = HttpServer::new("127.0.0.1", "8080").await.unwrap(); // Create a new http listener
+/// let http_server = HttpServer::<()>::new("127.0.0.1", "8080", ()).await.unwrap(); // Create a new http listener
 /// ```
-pub struct HttpServer{
+pub struct HttpServer<S>{
     listener: TcpListener,
-    pub routes: Routes,
+    pub routes: Routes<S>,
+    state: Arc<S>,
+    shutdown_tx: Option<tokio::sync::oneshot::Sender<()>>,
+    shutdown_rx: tokio::sync::oneshot::Receiver<()>,
 }
 
 
-impl HttpServer{
+impl<S> HttpServer<S> where S: Send + Sync + 'static{
 
     /// # New
-    /// 
-    /// Create a new server, with a given IP and port
-    /// 
+    ///
+    /// Create a new server, with a given IP, port and shared application state.
+    ///
     /// **Example**
     /// ```
-    /// let http_server = HttpServer::new("127.0.0.1", "8080").await.unwrap();
+    /// let http_server = HttpServer::<()>::new("127.0.0.1", "8080", ()).await.unwrap();
     /// ```
-    pub async fn new(ip: &str, port: &str) -> io::Result<Self>{
+    pub async fn new(ip: &str, port: &str, state: S) -> io::Result<Self>{
         let address = format!("{}:{}", ip, port);
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
         Ok(Self{
             listener: TcpListener::bind(&address).await?,
             routes: Routes::new().await,
+            state: Arc::new(state),
+            shutdown_tx: Some(shutdown_tx),
+            shutdown_rx,
         })
     }
 
+    /// # Shutdown Handle
+    ///
+    /// Takes the sender half of the shutdown signal, which triggers a graceful
+    /// shutdown once sent to (eg `let _ = handle.send(());` from a Ctrl-C
+    /// handler spawned before calling `listen`). Once notified, `listen` stops
+    /// accepting new connections and waits for every in-flight connection to
+    /// finish before returning.
+    ///
+    /// Unlike `Notify::notify_waiters`, a `oneshot` doesn't lose the signal if
+    /// it's sent before `listen`'s select loop gets around to polling it - the
+    /// value just sits in the channel until then.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called more than once - there is only one shutdown signal to hand out.
+    pub fn shutdown_handle(&mut self) -> tokio::sync::oneshot::Sender<()>{
+        self.shutdown_tx.take().expect("shutdown_handle can only be called once")
+    }
+
     /// # Listen
-    /// 
-    /// Listen for new connections. 
-    /// 
-    /// Run `handle_connection` upon connection.
-    pub async fn listen(&mut self){
+    ///
+    /// Listen for new connections, spawning a task per accepted connection so
+    /// requests are handled concurrently rather than one at a time.
+    ///
+    /// Stops accepting once `shutdown_handle()` is notified, then waits for
+    /// every connection still in flight to finish before returning.
+    pub async fn listen(self){
+        let routes = Arc::new(self.routes);
+        let state = self.state;
+        let mut shutdown_rx = self.shutdown_rx;
+
+        // Each spawned connection task holds on to `drain_tx` for its whole
+        // lifetime. Once we stop accepting and drop our own sender, `drain_rx.recv()`
+        // only resolves once every in-flight connection has dropped its clone too -
+        // so there is no race where the last connection finishes without being waited on.
+        let (drain_tx, mut drain_rx) = tokio::sync::mpsc::channel::<()>(1);
+
         loop{
-            let (socket, addr) = self.listener.accept().await.unwrap(); // Accept an incoming connection
-            self.handle_connection(socket, addr).await.unwrap(); // Handle it
+            tokio::select!{
+                _ = &mut shutdown_rx => break,
+                accepted = self.listener.accept() => {
+                    let (socket, addr) = match accepted{
+                        Ok(pair) => pair,
+                        Err(e) => {
+                            println!("Error accepting connection: {}", e);
+                            continue;
+                        }
+                    };
+
+                    let routes = routes.clone();
+                    let state = state.clone();
+                    let drain_tx = drain_tx.clone();
+
+                    tokio::spawn(async move {
+                        if let Err(e) = Self::handle_connection(routes, state, socket, addr).await{
+                            println!("Error handling connection: {}", e);
+                        }
+                        drop(drain_tx);
+                    });
+                }
+            }
         }
+
+        drop(drain_tx);
+        let _ = drain_rx.recv().await; // resolves once every connection task has dropped its sender
     }
 
     /// # Handle Connection
-    /// 
+    ///
     /// This function takes a `TcpStream`, and runs all the necessary functions to read the request,
     /// handle the response and write it back to the user.
-    /// 
+    ///
     /// This function should only be called by the `HttpServer`, as it should only be run upon accepting
     /// a new connection
-    /// 
+    ///
     /// We define the content to return using the `Routes` struct in `HttpServer`
-    /// 
+    ///
     /// It returns a Result for better error handling if something goes wrong at any point during I/O operations
-    async fn handle_connection(&mut self, stream: TcpStream, addr: std::net::SocketAddr) -> Result<(), &str>{
-        
+    async fn handle_connection(routes: Arc<Routes<S>>, state: Arc<S>, stream: TcpStream, addr: std::net::SocketAddr) -> Result<(), &'static str>{
+
         let mut connection = Connection::new(stream); // Create our connection handler
 
-        let request_str = connection.read_to_string().await; // get a string value from the recieved data
+        let request_bytes = connection.read_request().await; // get the raw bytes off the wire
+
+        // Websocket upgrades take over the raw connection themselves, so they're
+        // detected before we hand anything to `Routes::get_route`
+        let request = Request::new(request_bytes.clone());
+        if request.is_websocket_upgrade(){
+            return Self::handle_websocket_upgrade(routes, state, connection, request).await;
+        }
 
         // only needs the request and address as it constructs a `Request` to get the route and more info
-        let ret_str = self.routes.get_route(request_str, addr).await.unwrap();
+        let ret_str = routes.get_route(request_bytes, addr, state).await.unwrap();
 
         match ret_str{
             crate::DataType::Text(text) => {
@@ -80,4 +156,37 @@ impl HttpServer{
 
         Ok(()) // Return the future
     }
+
+    /// # Handle Websocket Upgrade
+    ///
+    /// Completes the RFC 6455 handshake for a request that asked to be upgraded
+    /// (`Connection: Upgrade`, `Upgrade: websocket`, `Sec-WebSocket-Key`), then hands
+    /// the raw `TcpStream` off as a `WebSocket` to whichever handler `Routes` has
+    /// registered for the requested URI.
+    ///
+    /// If no websocket handler is registered for the URI, the handshake is refused
+    /// with a `404 Not Found`.
+    async fn handle_websocket_upgrade(routes: Arc<Routes<S>>, state: Arc<S>, mut connection: Connection, request: Request) -> Result<(), &'static str>{
+        let handler = match routes.get_ws_handler(&request.uri){
+            Some(handler) => handler,
+            None => {
+                connection.write_string(String::from("HTTP/1.1 404 Not Found\r\n\r\n")).await.unwrap();
+                return Ok(());
+            }
+        };
+
+        let key = request.sec_websocket_key.clone().unwrap_or_default();
+        let accept = websocket::accept_key(&key);
+
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept
+        );
+        connection.write_string(response).await.unwrap();
+
+        let socket = websocket::WebSocket::new(connection.into_inner());
+        handler(state, request, socket).await;
+
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,329 @@
+use tokio::net::TcpStream;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use sha1::{Sha1, Digest};
+use base64::Engine;
+
+/// The magic GUID defined by RFC 6455, concatenated onto the client's
+/// `Sec-WebSocket-Key` before hashing to produce `Sec-WebSocket-Accept`.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// The largest payload we'll allocate a buffer for in one frame. The 16-bit
+/// and 64-bit extended length fields are entirely client-controlled, so
+/// without a cap a single frame header claiming eg `u64::MAX` would trigger
+/// an allocation that aborts the whole process - not just this connection.
+const MAX_FRAME_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The largest reassembled message (across all fragments of a fragmented
+/// message) we'll buffer in `WebSocket::recv`. `MAX_FRAME_SIZE` only bounds a
+/// single frame - without this, a client could stream an unbounded number of
+/// `FIN=0` continuation frames and grow `fragments` until the process OOMs.
+const MAX_MESSAGE_SIZE: usize = 32 * 1024 * 1024;
+
+/// # Accept Key
+///
+/// Computes the `Sec-WebSocket-Accept` header value for a given
+/// `Sec-WebSocket-Key`, per RFC 6455 section 1.3.
+pub fn accept_key(client_key: &str) -> String{
+    let mut hasher = Sha1::new();
+    hasher.update(client_key.as_bytes());
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+
+    base64::engine::general_purpose::STANDARD.encode(hasher.finalize())
+}
+
+/// # Message
+///
+/// A single, fully reassembled WebSocket message handed to (or received from)
+/// user code. Ping/Pong/Close are handled transparently by `WebSocket::recv`
+/// where possible - `Close` is still surfaced so the caller knows to stop.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message{
+    Text(String),
+    Binary(Vec<u8>),
+    Close
+}
+
+/// # WebSocket
+///
+/// A handshake-complete WebSocket connection. Obtained from
+/// `HttpServer::handle_connection` once it has detected and answered an
+/// upgrade request, and handed to whichever callback `Routes` registered for
+/// the requested route.
+///
+/// Reads and writes RFC 6455 frames directly over the underlying `TcpStream`:
+/// fragmented messages are reassembled in `recv`, and `Ping` frames are
+/// answered with `Pong` automatically.
+pub struct WebSocket{
+    stream: TcpStream
+}
+
+impl WebSocket{
+    pub(crate) fn new(stream: TcpStream) -> Self{
+        Self{ stream }
+    }
+
+    /// # Recv
+    ///
+    /// Reads the next complete message from the client, reassembling
+    /// fragmented frames and answering `Ping` frames with `Pong` along the
+    /// way. Returns `Ok(None)` once the peer has closed the connection.
+    pub async fn recv(&mut self) -> std::io::Result<Option<Message>>{
+        let mut fragments: Vec<u8> = Vec::new();
+        let mut fragmented_opcode: Option<u8> = None;
+
+        loop{
+            let frame = match self.read_frame().await?{
+                Some(frame) => frame,
+                None => return Ok(None)
+            };
+
+            match frame.opcode{
+                OPCODE_PING => {
+                    self.write_frame(OPCODE_PONG, &frame.payload).await?;
+                    continue;
+                }
+                OPCODE_PONG => continue,
+                OPCODE_CLOSE => {
+                    self.write_frame(OPCODE_CLOSE, &frame.payload).await?;
+                    return Ok(Some(Message::Close));
+                }
+                OPCODE_CONTINUATION => {
+                    fragments.extend(frame.payload);
+                }
+                opcode => {
+                    fragmented_opcode = Some(opcode);
+                    fragments.extend(frame.payload);
+                }
+            }
+
+            if fragments.len() > MAX_MESSAGE_SIZE{
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("reassembled message of {} bytes exceeds the {} byte limit", fragments.len(), MAX_MESSAGE_SIZE)
+                ));
+            }
+
+            if frame.fin{
+                let opcode = match fragmented_opcode{
+                    Some(opcode) => opcode,
+                    None => {
+                        // A lone continuation frame with no preceding start frame -
+                        // discard whatever it contributed rather than letting it
+                        // leak into the next message.
+                        fragments.clear();
+                        continue;
+                    }
+                };
+
+                return Ok(Some(match opcode{
+                    OPCODE_TEXT => Message::Text(String::from_utf8_lossy(&fragments).into_owned()),
+                    _ => Message::Binary(fragments)
+                }));
+            }
+        }
+    }
+
+    /// # Send
+    ///
+    /// Sends a single, unfragmented message to the client.
+    pub async fn send(&mut self, message: Message) -> std::io::Result<()>{
+        match message{
+            Message::Text(text) => self.write_frame(OPCODE_TEXT, text.as_bytes()).await,
+            Message::Binary(bytes) => self.write_frame(OPCODE_BINARY, &bytes).await,
+            Message::Close => self.write_frame(OPCODE_CLOSE, &[]).await
+        }
+    }
+
+    async fn read_frame(&mut self) -> std::io::Result<Option<Frame>>{
+        let mut header = [0u8; 2];
+        if self.stream.read_exact(&mut header).await.is_err(){
+            return Ok(None);
+        }
+
+        let fin = header[0] & 0b1000_0000 != 0;
+        let opcode = header[0] & 0b0000_1111;
+        let masked = header[1] & 0b1000_0000 != 0;
+        let mut length = (header[1] & 0b0111_1111) as u64;
+
+        if length == 126{
+            let mut extended = [0u8; 2];
+            self.stream.read_exact(&mut extended).await?;
+            length = u16::from_be_bytes(extended) as u64;
+        }else if length == 127{
+            let mut extended = [0u8; 8];
+            self.stream.read_exact(&mut extended).await?;
+            length = u64::from_be_bytes(extended);
+        }
+
+        if length > MAX_FRAME_SIZE{
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("frame payload of {} bytes exceeds the {} byte limit", length, MAX_FRAME_SIZE)
+            ));
+        }
+
+        let mask = if masked{
+            let mut mask = [0u8; 4];
+            self.stream.read_exact(&mut mask).await?;
+            Some(mask)
+        }else{
+            None
+        };
+
+        let mut payload = vec![0u8; length as usize];
+        self.stream.read_exact(&mut payload).await?;
+
+        if let Some(mask) = mask{
+            for (i, byte) in payload.iter_mut().enumerate(){
+                *byte ^= mask[i % 4];
+            }
+        }
+
+        Ok(Some(Frame{ fin, opcode, payload }))
+    }
+
+    /// Writes a single, final (unfragmented), unmasked frame - servers must not
+    /// mask frames sent to the client, per RFC 6455 section 5.1.
+    async fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> std::io::Result<()>{
+        let mut frame = Vec::with_capacity(payload.len() + 10);
+        frame.push(0b1000_0000 | opcode);
+
+        let len = payload.len();
+        if len < 126{
+            frame.push(len as u8);
+        }else if len <= u16::MAX as usize{
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }else{
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+
+        frame.extend_from_slice(payload);
+
+        self.stream.write_all(&frame).await?;
+        self.stream.flush().await
+    }
+}
+
+struct Frame{
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// The canonical RFC 6455 section 1.3 test vector.
+    #[test]
+    fn accept_key_matches_rfc6455_example(){
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    /// Connects a loopback pair of `WebSocket`s so frame encode/decode can be
+    /// exercised over a real `TcpStream`. Frames sent this way are unmasked.
+    async fn loopback_pair() -> (WebSocket, WebSocket){
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = TcpStream::connect(addr).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+
+        (WebSocket::new(client), WebSocket::new(server))
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_text_round_trips(){
+        let (mut client, mut server) = loopback_pair().await;
+
+        server.send(Message::Text("hello".to_string())).await.unwrap();
+        let received = client.recv().await.unwrap();
+
+        assert_eq!(received, Some(Message::Text("hello".to_string())));
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_binary_round_trips(){
+        let (mut client, mut server) = loopback_pair().await;
+
+        server.send(Message::Binary(vec![1, 2, 3, 4])).await.unwrap();
+        let received = client.recv().await.unwrap();
+
+        assert_eq!(received, Some(Message::Binary(vec![1, 2, 3, 4])));
+    }
+
+    #[tokio::test]
+    async fn fragmented_message_is_reassembled(){
+        let (mut client, mut server) = loopback_pair().await;
+
+        // A non-final text frame (FIN=0) followed by a final continuation frame.
+        server.stream.write_all(&{
+            let mut frame = vec![OPCODE_TEXT, 3];
+            frame.extend_from_slice(b"hel");
+            frame
+        }).await.unwrap();
+        server.stream.write_all(&{
+            let mut frame = vec![0b1000_0000 | OPCODE_CONTINUATION, 2];
+            frame.extend_from_slice(b"lo");
+            frame
+        }).await.unwrap();
+
+        let received = client.recv().await.unwrap();
+        assert_eq!(received, Some(Message::Text("hello".to_string())));
+    }
+
+    #[tokio::test]
+    async fn oversized_frame_is_rejected(){
+        let (mut client, mut server) = loopback_pair().await;
+
+        // Hand-craft a frame header claiming a payload far past MAX_FRAME_SIZE.
+        let mut header = vec![0b1000_0000 | OPCODE_BINARY, 127];
+        header.extend_from_slice(&u64::MAX.to_be_bytes());
+        server.stream.write_all(&header).await.unwrap();
+
+        let result = client.read_frame().await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn oversized_reassembled_message_is_rejected(){
+        let (mut client, mut server) = loopback_pair().await;
+
+        // Three frames, each one byte under MAX_FRAME_SIZE on its own, whose combined
+        // payload exceeds MAX_MESSAGE_SIZE - the per-frame cap alone wouldn't
+        // catch this, only the running total across fragments does. Written from
+        // a separate task so the writes can't block on a client that isn't
+        // draining the socket while it's still filling fragments.
+        let chunk_size = (MAX_FRAME_SIZE as usize) - 1;
+        let writer = tokio::spawn(async move{
+            let chunk = vec![0u8; chunk_size];
+
+            let mut first = vec![OPCODE_BINARY, 127];
+            first.extend_from_slice(&(chunk.len() as u64).to_be_bytes());
+            server.stream.write_all(&first).await.unwrap();
+            server.stream.write_all(&chunk).await.unwrap();
+
+            for _ in 0..2{
+                let mut frame = vec![OPCODE_CONTINUATION, 127];
+                frame.extend_from_slice(&(chunk.len() as u64).to_be_bytes());
+                server.stream.write_all(&frame).await.unwrap();
+                server.stream.write_all(&chunk).await.unwrap();
+            }
+        });
+
+        let result = client.recv().await;
+        assert!(result.is_err());
+
+        writer.abort();
+    }
+}